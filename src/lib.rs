@@ -1,8 +1,23 @@
 // Copyright (c) 2024 Ivan Guerreschi. All rights reserved.
 // Licensed under the MIT License. See LICENSE in the project root for license information.
 
-use std::path::Path;
-use std::{fs, io};
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub mod trash;
+
+/// Options controlling where `process_file` stashes things and whether it
+/// actually touches the filesystem.
+#[derive(Debug, Default, Clone)]
+pub struct Options {
+    /// Overrides the trash root, which otherwise defaults to `srm_trash`
+    /// under the system temp dir resolved at runtime (see
+    /// [`trash::trash_dir`]).
+    pub dest_dir: Option<PathBuf>,
+    /// When set, report what would be moved without copying or deleting
+    /// anything.
+    pub dry_run: bool,
+}
 
 // Create a custom error enum to handle different error types
 #[derive(Debug)]
@@ -10,6 +25,12 @@ pub enum FileError {
     NoFileSpecified,
     CopyError(io::Error),
     DeleteError(io::Error),
+    RenameError(io::Error),
+    WalkError(io::Error),
+    NoMatches(String),
+    IndexError(io::Error),
+    IndexCorrupt(String),
+    NoTrashEntry(String),
 }
 
 // Implement the error trait for our custom error type
@@ -22,52 +43,57 @@ impl std::fmt::Display for FileError {
             FileError::NoFileSpecified => write!(f, "No input file specified"),
             FileError::CopyError(err) => write!(f, "Failed to copy file: {}", err),
             FileError::DeleteError(err) => write!(f, "Failed to delete original file: {}", err),
+            FileError::RenameError(err) => write!(f, "Failed to rename file: {}", err),
+            FileError::WalkError(err) => write!(f, "Failed to walk directory: {}", err),
+            FileError::NoMatches(pattern) => {
+                write!(f, "Pattern '{}' did not match any files", pattern)
+            }
+            FileError::IndexError(err) => write!(f, "Failed to access trash index: {}", err),
+            FileError::IndexCorrupt(line) => {
+                write!(f, "Trash index is corrupt at entry: {}", line)
+            }
+            FileError::NoTrashEntry(path) => {
+                write!(f, "No trash entry found for '{}'", path)
+            }
         }
     }
 }
 
-/// Process a file by copying it to /tmp and deleting the original
-pub fn process_file(file: &str) -> Result<(), FileError> {
+/// Process a file or directory by moving it into the trash and recording
+/// the move in the trash index so it can be restored with `restore` later.
+///
+/// With `options.dry_run` set, nothing is copied, deleted, or recorded;
+/// `trash::stash` only reports the destination it would have used.
+pub fn process_file(file: &str, options: &Options) -> Result<(), FileError> {
     // Validate the input file exists
     if !Path::new(file).exists() {
         eprintln!("Error: Input file '{}' does not exist", file);
         return Err(FileError::NoFileSpecified);
     }
 
-    // Create destination path
-    let to_path = format!("/tmp/{}_copy", file);
-
-    // Copy the file, map the io::Error to our custom error type
-    match copy_file(file, &to_path) {
-        Ok(bytes) => println!("Successfully copied {} bytes to {}", bytes, to_path),
-        Err(e) => {
-            eprintln!("Error during file copy: {}", e);
-            return Err(e);
-        }
+    let stashed_path = trash::stash(file, options)?;
+    if options.dry_run {
+        println!("[dry-run] Would move {} to {}", file, stashed_path.display());
+    } else {
+        println!("Moved {} to {}", file, stashed_path.display());
     }
-
-    // Delete the original file
-    delete_file(file)?;
-
-    println!("Original file successfully deleted");
     Ok(())
 }
 
 /// Helper function to copy file
 pub fn copy_file(from: &str, to: &str) -> Result<u64, FileError> {
-    fs::copy(from, to).map_err(FileError::CopyError)
+    std::fs::copy(from, to).map_err(FileError::CopyError)
 }
 
 /// Helper function to delete file
 pub fn delete_file(file: &str) -> Result<(), FileError> {
-    fs::remove_file(file).map_err(FileError::DeleteError)
+    std::fs::remove_file(file).map_err(FileError::DeleteError)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use fs::File;
-    use std::env;
+    use std::fs::{self, File};
     use std::io::Write;
     use tempfile::TempDir;
 
@@ -149,18 +175,39 @@ mod tests {
     }
 
     #[test]
-    fn test_process_file() {
+    fn test_process_file_stashes_and_restores() {
         let file_name = "workflow_test.txt";
         let temp_dir = TempDir::new().unwrap();
         let source_path = create_temp_file(&temp_dir, file_name, b"test workflow").unwrap();
 
-        let _ = env::set_current_dir(temp_dir.path());
-
-        let result = process_file(file_name);
+        let _ = std::env::set_current_dir(temp_dir.path());
 
+        let options = Options::default();
+        let result = process_file(file_name, &options);
         assert!(result.is_ok());
         assert!(!Path::new(&source_path).exists());
 
-        let _ = fs::remove_file(format!("/tmp/{}{}", file_name, "_copy"));
+        let restored = trash::restore(file_name, &options).unwrap();
+        assert_eq!(fs::read_to_string(&restored).unwrap(), "test workflow");
+        let _ = fs::remove_file(&restored);
+    }
+
+    #[test]
+    fn test_process_file_dry_run_leaves_source_and_destination_untouched() {
+        let file_name = "dry_run_test.txt";
+        let temp_dir = TempDir::new().unwrap();
+        let source_path = create_temp_file(&temp_dir, file_name, b"do not move me").unwrap();
+
+        // Use the absolute path: the other process_file test changes the
+        // process-wide current directory, and tests run in parallel.
+        let options = Options {
+            dest_dir: Some(temp_dir.path().join("dest")),
+            dry_run: true,
+        };
+        let result = process_file(&source_path, &options);
+
+        assert!(result.is_ok());
+        assert!(Path::new(&source_path).exists());
+        assert!(!temp_dir.path().join("dest").exists());
     }
 }
@@ -1,22 +1,134 @@
 // Copyright (c) 2024 Ivan Guerreschi. All rights reserved.
 // Licensed under the MIT License. See LICENSE in the project root for license information.
 
+use srm::{FileError, Options};
 use std::env;
-use srm::FileError; 
-
-fn main() -> Result<(), FileError> {
-    // Get the file path from command line arguments
-    let file = get_input_file()?;
-    
-    // Process the file
-    srm::process_file(&file)
+use std::path::PathBuf;
+
+fn main() {
+    let raw_args: Vec<String> = env::args().skip(1).collect();
+
+    if raw_args.first().map(|arg| arg.as_str()) == Some("restore") {
+        let (options, targets) = parse_options(raw_args[1..].to_vec());
+        run_restore(targets, &options);
+        return;
+    }
+
+    let (options, patterns) = parse_options(raw_args);
+
+    if patterns.is_empty() {
+        eprintln!("Error: {}", FileError::NoFileSpecified);
+        std::process::exit(1);
+    }
+
+    run_trash(patterns, &options);
 }
 
-// Helper function to get input file from command line
-fn get_input_file() -> Result<String, FileError> {
-    env::args()
-        .nth(1)
-        .ok_or(FileError::NoFileSpecified)
+// Pull `--dest <DIR>` and `--dry-run` out of `args`, returning the parsed
+// Options alongside whatever's left (paths, glob patterns, or restore
+// targets).
+fn parse_options(args: Vec<String>) -> (Options, Vec<String>) {
+    let mut options = Options::default();
+    let mut rest = Vec::new();
+    let mut iter = args.into_iter();
+
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dest" => {
+                if let Some(dir) = iter.next() {
+                    options.dest_dir = Some(PathBuf::from(dir));
+                }
+            }
+            "--dry-run" => options.dry_run = true,
+            _ => rest.push(arg),
+        }
+    }
+
+    (options, rest)
+}
+
+// Move every path matched by `patterns` into the trash, reporting a
+// succeeded/failed summary and exiting non-zero if anything failed.
+fn run_trash(patterns: Vec<String>, options: &Options) {
+    let mut succeeded = 0usize;
+    let mut failed = 0usize;
+
+    for pattern in patterns {
+        match expand_pattern(&pattern) {
+            Ok(paths) => {
+                for path in paths {
+                    match srm::process_file(&path, options) {
+                        Ok(()) => succeeded += 1,
+                        Err(err) => {
+                            eprintln!("Error processing '{}': {}", path, err);
+                            failed += 1;
+                        }
+                    }
+                }
+            }
+            Err(err) => {
+                eprintln!("Error: {}", err);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} succeeded, {} failed", succeeded, failed);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// Restore the most recently trashed entry for each original path in
+// `targets`, reporting the same kind of summary as `run_trash`.
+fn run_restore(targets: Vec<String>, options: &Options) {
+    if targets.is_empty() {
+        eprintln!("Error: {}", FileError::NoFileSpecified);
+        std::process::exit(1);
+    }
+
+    let mut succeeded = 0usize;
+    let mut failed = 0usize;
+
+    for target in targets {
+        match srm::trash::restore(&target, options) {
+            Ok(restored_path) => {
+                if options.dry_run {
+                    println!("[dry-run] Would restore {}", restored_path.display());
+                } else {
+                    println!("Restored {}", restored_path.display());
+                }
+                succeeded += 1;
+            }
+            Err(err) => {
+                eprintln!("Error restoring '{}': {}", target, err);
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{} succeeded, {} failed", succeeded, failed);
+
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// Expand a single path or glob pattern (`*.log`, `src/**/*.rs`) into the
+// list of paths it matches on disk.
+fn expand_pattern(pattern: &str) -> Result<Vec<String>, FileError> {
+    let paths: Vec<String> = glob::glob(pattern)
+        .map_err(|err| FileError::NoMatches(format!("{}: {}", pattern, err)))?
+        .filter_map(|entry| entry.ok())
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+
+    if paths.is_empty() {
+        return Err(FileError::NoMatches(pattern.to_string()));
+    }
+
+    Ok(paths)
 }
 
 #[cfg(test)]
@@ -24,7 +136,35 @@ mod tests {
     use super::*;
 
     #[test]
-    fn test_get_input_file_no_args() {
-        assert!(matches!(get_input_file(), Err(FileError::NoFileSpecified)));
+    fn test_parse_options_extracts_dest_and_dry_run() {
+        let args = vec![
+            "--dry-run".to_string(),
+            "--dest".to_string(),
+            "/tmp/custom".to_string(),
+            "file.txt".to_string(),
+        ];
+
+        let (options, rest) = parse_options(args);
+
+        assert!(options.dry_run);
+        assert_eq!(options.dest_dir, Some(PathBuf::from("/tmp/custom")));
+        assert_eq!(rest, vec!["file.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_parse_options_defaults_when_no_flags_present() {
+        let args = vec!["file.txt".to_string(), "other.txt".to_string()];
+
+        let (options, rest) = parse_options(args);
+
+        assert!(!options.dry_run);
+        assert_eq!(options.dest_dir, None);
+        assert_eq!(rest, vec!["file.txt".to_string(), "other.txt".to_string()]);
+    }
+
+    #[test]
+    fn test_expand_pattern_no_matches() {
+        let result = expand_pattern("/nonexistent/path/that/should/not/exist_*.nope");
+        assert!(matches!(result, Err(FileError::NoMatches(_))));
     }
 }
@@ -0,0 +1,620 @@
+// Copyright (c) 2024 Ivan Guerreschi. All rights reserved.
+// Licensed under the MIT License. See LICENSE in the project root for license information.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs as unix_fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use walkdir::WalkDir;
+
+use crate::{FileError, Options};
+
+const INDEX_FILE_NAME: &str = "index.tsv";
+
+// Guards the read-modify-write of the trash index so two stash/restore
+// calls in the same process can't race and clobber each other's entries.
+static INDEX_LOCK: Mutex<()> = Mutex::new(());
+
+/// One record in the trash index: where a file originally lived, the name
+/// it was given inside the trash directory, and when it was stashed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashEntry {
+    pub original_path: PathBuf,
+    pub stored_name: String,
+    pub timestamp: u64,
+}
+
+impl TrashEntry {
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}",
+            self.timestamp,
+            self.stored_name,
+            self.original_path.display()
+        )
+    }
+
+    fn from_line(line: &str) -> Result<Self, FileError> {
+        let mut parts = line.splitn(3, '\t');
+        let timestamp = parts
+            .next()
+            .and_then(|s| s.parse::<u64>().ok())
+            .ok_or_else(|| FileError::IndexCorrupt(line.to_string()))?;
+        let stored_name = parts
+            .next()
+            .ok_or_else(|| FileError::IndexCorrupt(line.to_string()))?
+            .to_string();
+        let original_path = parts
+            .next()
+            .ok_or_else(|| FileError::IndexCorrupt(line.to_string()))?;
+
+        Ok(TrashEntry {
+            original_path: PathBuf::from(original_path),
+            stored_name,
+            timestamp,
+        })
+    }
+}
+
+/// The default trash directory the tool stashes files into and restores
+/// them from: `srm_trash` under the system temp dir resolved at runtime.
+pub fn trash_dir() -> PathBuf {
+    std::env::temp_dir().join("srm_trash")
+}
+
+/// The trash directory to use for a given set of options: `options.dest_dir`
+/// if set, otherwise the default from [`trash_dir`].
+fn resolve_trash_dir(options: &Options) -> PathBuf {
+    options.dest_dir.clone().unwrap_or_else(trash_dir)
+}
+
+fn index_path(dir: &Path) -> PathBuf {
+    dir.join(INDEX_FILE_NAME)
+}
+
+/// Load the trash index, returning an empty list if it doesn't exist yet.
+pub fn load_index(dir: &Path) -> Result<Vec<TrashEntry>, FileError> {
+    let path = index_path(dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let file = File::open(&path).map_err(FileError::IndexError)?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(l) if l.is_empty()))
+        .map(|line| TrashEntry::from_line(&line.map_err(FileError::IndexError)?))
+        .collect()
+}
+
+/// Overwrite the trash index with `entries`.
+pub fn save_index(dir: &Path, entries: &[TrashEntry]) -> Result<(), FileError> {
+    fs::create_dir_all(dir).map_err(FileError::IndexError)?;
+    let mut file = File::create(index_path(dir)).map_err(FileError::IndexError)?;
+    for entry in entries {
+        writeln!(file, "{}", entry.to_line()).map_err(FileError::IndexError)?;
+    }
+    Ok(())
+}
+
+/// Move `file` into the trash directory under a collision-free name and
+/// append a record to the trash index. Returns the path the file was (or,
+/// in `options.dry_run` mode, would be) stashed at; dry-run never touches
+/// the filesystem or the index.
+pub fn stash(file: &str, options: &Options) -> Result<PathBuf, FileError> {
+    let dir = resolve_trash_dir(options);
+
+    let original_path = absolute_path(file).map_err(FileError::IndexError)?;
+    let file_name = original_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("file");
+
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let stored_path = unique_destination(&dir, file_name);
+
+    if options.dry_run {
+        return Ok(stored_path);
+    }
+
+    fs::create_dir_all(&dir).map_err(FileError::IndexError)?;
+    let stored_name = stored_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(file_name)
+        .to_string();
+
+    move_path(Path::new(file), &stored_path)?;
+
+    let mut entries = load_index(&dir)?;
+    entries.push(TrashEntry {
+        original_path,
+        stored_name,
+        timestamp: now_secs(),
+    });
+    save_index(&dir, &entries)?;
+
+    Ok(stored_path)
+}
+
+/// Restore the most recently trashed entry whose original path matches
+/// `original`, moving it back and recreating any missing parent
+/// directories. Returns the path it was (or, in `options.dry_run` mode,
+/// would be) restored to; dry-run never touches the filesystem or the
+/// index.
+pub fn restore(original: &str, options: &Options) -> Result<PathBuf, FileError> {
+    let dir = resolve_trash_dir(options);
+    let target = absolute_path(original).map_err(FileError::IndexError)?;
+
+    let _guard = INDEX_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+    let mut entries = load_index(&dir)?;
+
+    let position = entries
+        .iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.original_path == target)
+        .max_by_key(|(_, entry)| entry.timestamp)
+        .map(|(index, _)| index)
+        .ok_or_else(|| FileError::NoTrashEntry(original.to_string()))?;
+
+    if options.dry_run {
+        return Ok(entries[position].original_path.clone());
+    }
+
+    let entry = entries.remove(position);
+    let stored_path = dir.join(&entry.stored_name);
+
+    if let Some(parent) = entry.original_path.parent() {
+        fs::create_dir_all(parent).map_err(FileError::IndexError)?;
+    }
+
+    move_path(&stored_path, &entry.original_path)?;
+    save_index(&dir, &entries)?;
+
+    Ok(entry.original_path)
+}
+
+/// Move `src` to `dest`, whether it's a regular file or a directory tree.
+fn move_path(src: &Path, dest: &Path) -> Result<(), FileError> {
+    if src.is_dir() {
+        move_directory(src, dest)
+    } else {
+        move_file(src, dest)
+    }
+}
+
+/// Move a single file, preferring an atomic `fs::rename` and only falling
+/// back to copy-then-delete when source and destination don't share a
+/// filesystem.
+fn move_file(src: &Path, dest: &Path) -> Result<(), FileError> {
+    match fs::rename(src, dest) {
+        Ok(()) => return Ok(()),
+        Err(err) if is_cross_device(&err) => {
+            // Different filesystems: fall through to the copy-then-delete
+            // fallback below.
+        }
+        Err(err) => return Err(FileError::RenameError(err)),
+    }
+
+    copy_file_atomic(src, dest)?;
+    fs::remove_file(src).map_err(FileError::DeleteError)
+}
+
+/// Recursively move a directory tree: recreate its structure at the
+/// destination, copy every regular file and preserve symlinks as-is (rather
+/// than following them out of the tree), then remove the original
+/// bottom-up so directories are empty by the time we try to remove them.
+fn move_directory(src: &Path, dest: &Path) -> Result<(), FileError> {
+    for entry in WalkDir::new(src) {
+        let entry = entry.map_err(to_walk_error)?;
+        let relative = entry
+            .path()
+            .strip_prefix(src)
+            .expect("walkdir entries are always under src");
+        let dest_path = dest.join(relative);
+        let file_type = entry.file_type();
+
+        if file_type.is_dir() {
+            fs::create_dir_all(&dest_path).map_err(FileError::CopyError)?;
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(entry.path()).map_err(FileError::CopyError)?;
+            unix_fs::symlink(&target, &dest_path).map_err(FileError::CopyError)?;
+        } else {
+            fs::copy(entry.path(), &dest_path).map_err(FileError::CopyError)?;
+        }
+    }
+
+    for entry in WalkDir::new(src).contents_first(true) {
+        let entry = entry.map_err(to_walk_error)?;
+        if entry.file_type().is_dir() {
+            fs::remove_dir(entry.path()).map_err(FileError::DeleteError)?;
+        } else {
+            fs::remove_file(entry.path()).map_err(FileError::DeleteError)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Copy `src` into the destination directory without ever leaving a
+/// half-written file at `dest`: copy into a uniquely-named temp file next
+/// to `dest`, fsync it, then rename it into place. The rename is atomic, so
+/// a crash mid-copy leaves the temp file orphaned rather than corrupting
+/// `dest`.
+fn copy_file_atomic(src: &Path, dest: &Path) -> Result<u64, FileError> {
+    let parent = dest.parent().unwrap_or_else(|| Path::new("."));
+    let tmp_name = format!(
+        ".{}.tmp{}",
+        dest.file_name().and_then(|n| n.to_str()).unwrap_or("srm"),
+        unique_suffix()
+    );
+    let tmp_path = parent.join(tmp_name);
+
+    let bytes = fs::copy(src, &tmp_path).map_err(FileError::CopyError)?;
+    File::open(&tmp_path)
+        .and_then(|f| f.sync_all())
+        .map_err(FileError::CopyError)?;
+    fs::rename(&tmp_path, dest).map_err(FileError::RenameError)?;
+
+    Ok(bytes)
+}
+
+/// True if `err` indicates the two paths involved don't share a filesystem,
+/// i.e. the case `fs::rename` can't handle and we must copy instead.
+fn is_cross_device(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::CrossesDevices | io::ErrorKind::InvalidInput
+    )
+}
+
+/// A suffix unlikely to collide between concurrent temp files or trashed
+/// entries, built from the process id and current time.
+fn unique_suffix() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("{}-{}", std::process::id(), nanos)
+}
+
+/// Pick a destination for `file_name` inside `dir` that doesn't already
+/// exist. `file_name` must be just the file name component (not a path),
+/// so a source path containing separators can't escape `dir` or produce an
+/// invalid name. We try a suffix derived from the process id and current
+/// time first; in the rare case that's already taken, fall back to an
+/// incrementing counter.
+fn unique_destination(dir: &Path, file_name: &str) -> PathBuf {
+    let mut candidate = dir.join(format!("{}-{}", unique_suffix(), file_name));
+    let mut attempt = 0u32;
+    while candidate.exists() {
+        attempt += 1;
+        candidate = dir.join(format!("{}-{}-{}", unique_suffix(), attempt, file_name));
+    }
+    candidate
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Resolve `path` to an absolute path without requiring it to exist, so
+/// restore can match entries whose original file is already gone.
+fn absolute_path(path: &str) -> io::Result<PathBuf> {
+    let candidate = Path::new(path);
+    if candidate.is_absolute() {
+        Ok(candidate.to_path_buf())
+    } else {
+        Ok(std::env::current_dir()?.join(candidate))
+    }
+}
+
+/// Convert a `walkdir::Error` into our own error type, preserving the
+/// underlying `io::Error` where one is available.
+fn to_walk_error(err: walkdir::Error) -> FileError {
+    match err.into_io_error() {
+        Some(io_err) => FileError::WalkError(io_err),
+        None => FileError::WalkError(io::Error::other("directory walk failed")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn create_temp_file(dir: &TempDir, filename: &str, content: &[u8]) -> PathBuf {
+        let file_path = dir.path().join(filename);
+        let mut file = File::create(&file_path).unwrap();
+        file.write_all(content).unwrap();
+        file_path
+    }
+
+    #[test]
+    fn test_trash_entry_round_trips_through_a_line() {
+        let entry = TrashEntry {
+            original_path: PathBuf::from("/home/user/notes.txt"),
+            stored_name: "123-notes.txt".to_string(),
+            timestamp: 42,
+        };
+
+        let line = entry.to_line();
+        let parsed = TrashEntry::from_line(&line).unwrap();
+
+        assert_eq!(parsed, entry);
+    }
+
+    #[test]
+    fn test_trash_entry_from_line_rejects_malformed_input() {
+        let result = TrashEntry::from_line("not-a-timestamp\tstored-name");
+        assert!(matches!(result, Err(FileError::IndexCorrupt(_))));
+    }
+
+    #[test]
+    fn test_save_and_load_index_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let entries = vec![
+            TrashEntry {
+                original_path: PathBuf::from("/a/one.txt"),
+                stored_name: "1-one.txt".to_string(),
+                timestamp: 1,
+            },
+            TrashEntry {
+                original_path: PathBuf::from("/a/two.txt"),
+                stored_name: "2-two.txt".to_string(),
+                timestamp: 2,
+            },
+        ];
+
+        save_index(temp_dir.path(), &entries).unwrap();
+        let loaded = load_index(temp_dir.path()).unwrap();
+
+        assert_eq!(loaded, entries);
+    }
+
+    #[test]
+    fn test_load_index_missing_file_returns_empty() {
+        let temp_dir = TempDir::new().unwrap();
+        let loaded = load_index(temp_dir.path()).unwrap();
+        assert!(loaded.is_empty());
+    }
+
+    #[test]
+    fn test_stash_and_restore_round_trip() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = create_temp_file(&temp_dir, "keepsake.txt", b"trash me");
+        let file_path_str = file_path.to_string_lossy().into_owned();
+
+        let stashed_path = stash(&file_path_str, &Options::default()).unwrap();
+        assert!(!file_path.exists());
+        assert_eq!(fs::read_to_string(&stashed_path).unwrap(), "trash me");
+
+        let restored_path = restore(&file_path_str, &Options::default()).unwrap();
+        assert_eq!(restored_path, file_path);
+        assert_eq!(fs::read_to_string(&restored_path).unwrap(), "trash me");
+    }
+
+    #[test]
+    fn test_restore_missing_entry_fails() {
+        let result = restore("/definitely/not/a/trashed/file.txt", &Options::default());
+        assert!(matches!(result, Err(FileError::NoTrashEntry(_))));
+    }
+
+    #[test]
+    fn test_stash_nested_path_uses_only_file_name() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("a/b")).unwrap();
+        let file_path = create_temp_file(&temp_dir, "a/b/report.txt", b"nested content");
+        let file_path_str = file_path.to_string_lossy().into_owned();
+
+        let stashed_path = stash(&file_path_str, &Options::default()).unwrap();
+
+        // The stashed name must be a single valid component derived from
+        // the file name, not the nested source path.
+        assert_eq!(stashed_path.parent().unwrap(), trash_dir());
+        assert!(stashed_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with("-report.txt"));
+        assert_eq!(
+            fs::read_to_string(&stashed_path).unwrap(),
+            "nested content"
+        );
+
+        let _ = restore(&file_path_str, &Options::default());
+    }
+
+    #[test]
+    fn test_unique_destination_avoids_clobbering_same_name() {
+        let temp_dir = TempDir::new().unwrap();
+        let first = unique_destination(temp_dir.path(), "dup.txt");
+        fs::write(&first, b"first").unwrap();
+        let second = unique_destination(temp_dir.path(), "dup.txt");
+
+        assert_ne!(first, second);
+        assert!(!second.exists());
+    }
+
+    #[test]
+    fn test_successive_stashes_of_same_named_files_do_not_clobber() {
+        let source_a = TempDir::new().unwrap();
+        let source_b = TempDir::new().unwrap();
+        let file_a = create_temp_file(&source_a, "duplicate.txt", b"from a");
+        let file_b = create_temp_file(&source_b, "duplicate.txt", b"from b");
+        let file_a_str = file_a.to_string_lossy().into_owned();
+        let file_b_str = file_b.to_string_lossy().into_owned();
+
+        let stashed_a = stash(&file_a_str, &Options::default()).unwrap();
+        let stashed_b = stash(&file_b_str, &Options::default()).unwrap();
+
+        assert_ne!(stashed_a, stashed_b);
+        assert_eq!(fs::read_to_string(&stashed_a).unwrap(), "from a");
+        assert_eq!(fs::read_to_string(&stashed_b).unwrap(), "from b");
+
+        let _ = restore(&file_a_str, &Options::default());
+        let _ = restore(&file_b_str, &Options::default());
+    }
+
+    #[test]
+    fn test_stash_honors_custom_dest_dir() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let file_path = create_temp_file(&source_dir, "custom_dest.txt", b"elsewhere");
+        let file_path_str = file_path.to_string_lossy().into_owned();
+
+        let options = Options {
+            dest_dir: Some(dest_dir.path().to_path_buf()),
+            dry_run: false,
+        };
+
+        let stashed_path = stash(&file_path_str, &options).unwrap();
+
+        assert_eq!(stashed_path.parent().unwrap(), dest_dir.path());
+        assert!(!file_path.exists());
+
+        let _ = restore(&file_path_str, &options);
+    }
+
+    #[test]
+    fn test_stash_dry_run_leaves_source_and_index_untouched() {
+        let source_dir = TempDir::new().unwrap();
+        let dest_dir = TempDir::new().unwrap();
+        let file_path = create_temp_file(&source_dir, "dry_run.txt", b"stay put");
+        let file_path_str = file_path.to_string_lossy().into_owned();
+
+        let options = Options {
+            dest_dir: Some(dest_dir.path().to_path_buf()),
+            dry_run: true,
+        };
+
+        let planned_path = stash(&file_path_str, &options).unwrap();
+
+        assert!(file_path.exists());
+        assert!(!planned_path.exists());
+        assert!(load_index(dest_dir.path()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_move_file_uses_rename_on_same_filesystem() {
+        // Both paths live under the same TempDir, so this should take the
+        // rename path and never touch copy_file_atomic.
+        let temp_dir = TempDir::new().unwrap();
+        let source_path = create_temp_file(&temp_dir, "rename_test.txt", b"rename me");
+        let dest_path = temp_dir.path().join("rename_test_dest.txt");
+
+        let result = move_file(&source_path, &dest_path);
+
+        assert!(result.is_ok());
+        assert!(!source_path.exists());
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), "rename me");
+    }
+
+    #[test]
+    fn test_copy_file_atomic_forced_cross_device_fallback() {
+        // Exercises the fallback path used when fs::rename can't cross
+        // filesystems: copy into a temp file beside the destination, fsync,
+        // then rename it into place.
+        let temp_dir = TempDir::new().unwrap();
+        let source_path =
+            create_temp_file(&temp_dir, "cross_device.txt", b"fallback content");
+        let dest_path = temp_dir.path().join("cross_device_copy.txt");
+
+        let result = copy_file_atomic(&source_path, &dest_path);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), b"fallback content".len() as u64);
+
+        assert_eq!(fs::read_to_string(&dest_path).unwrap(), "fallback content");
+        // The fallback only copies; the caller is responsible for deleting
+        // the original afterwards.
+        assert!(source_path.exists());
+
+        // No stray temp file should remain in the destination directory.
+        let leftovers: Vec<_> = fs::read_dir(temp_dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with(".cross_device_copy.txt.tmp")
+            })
+            .collect();
+        assert!(leftovers.is_empty());
+    }
+
+    #[test]
+    fn test_copy_file_atomic_then_remove_completes_fallback_move() {
+        // move_file can't be handed a real EXDEV in a unit test, so this
+        // replays the steps of its fallback by hand: copy_file_atomic, then
+        // delete the original, the way move_file does after a cross-device
+        // rename error. move_file itself is not called here.
+        let temp_dir = TempDir::new().unwrap();
+        let source_path =
+            create_temp_file(&temp_dir, "cross_device_move.txt", b"move me across");
+        let dest_path = temp_dir.path().join("cross_device_move_dest.txt");
+
+        copy_file_atomic(&source_path, &dest_path).unwrap();
+        fs::remove_file(&source_path).unwrap();
+
+        assert!(!source_path.exists());
+        assert_eq!(
+            fs::read_to_string(&dest_path).unwrap(),
+            "move me across"
+        );
+    }
+
+    #[test]
+    fn test_is_cross_device_detects_relevant_kinds() {
+        let crosses = io::Error::new(io::ErrorKind::CrossesDevices, "exdev");
+        assert!(is_cross_device(&crosses));
+
+        let invalid = io::Error::new(io::ErrorKind::InvalidInput, "invalid");
+        assert!(is_cross_device(&invalid));
+
+        let other = io::Error::new(io::ErrorKind::NotFound, "missing");
+        assert!(!is_cross_device(&other));
+    }
+
+    #[test]
+    fn test_move_directory_recreates_nested_tree_with_symlink_and_removes_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let src_root = temp_dir.path().join("move_dir_src");
+        let dest_root = temp_dir.path().join("move_dir_dest");
+
+        fs::create_dir_all(src_root.join("nested/inner")).unwrap();
+        fs::write(src_root.join("top.txt"), b"top level").unwrap();
+        fs::write(src_root.join("nested/mid.txt"), b"mid level").unwrap();
+        fs::write(src_root.join("nested/inner/leaf.txt"), b"leaf level").unwrap();
+        unix_fs::symlink("mid.txt", src_root.join("nested/link_to_mid.txt")).unwrap();
+
+        let result = move_directory(&src_root, &dest_root);
+        assert!(result.is_ok());
+        assert!(!src_root.exists());
+
+        assert_eq!(
+            fs::read_to_string(dest_root.join("top.txt")).unwrap(),
+            "top level"
+        );
+        assert_eq!(
+            fs::read_to_string(dest_root.join("nested/mid.txt")).unwrap(),
+            "mid level"
+        );
+        assert_eq!(
+            fs::read_to_string(dest_root.join("nested/inner/leaf.txt")).unwrap(),
+            "leaf level"
+        );
+
+        let link_path = dest_root.join("nested/link_to_mid.txt");
+        assert!(fs::symlink_metadata(&link_path).unwrap().file_type().is_symlink());
+        assert_eq!(fs::read_link(&link_path).unwrap(), Path::new("mid.txt"));
+    }
+}